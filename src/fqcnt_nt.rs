@@ -2,38 +2,632 @@
 // Based on biofast benchmark: https://github.com/lh3/biofast
 
 //use needletail::{parse_fastx_file, Sequence}; // ignore unused_imports
-use needletail::{parse_fastx_file}; // ignore unused_imports
+use needletail::{parse_fastx_file, parse_fastx_stdin};
+use needletail::parser::FastxReader;
+use memmap2::Mmap;
 use std::env;
-use std::io::{self, Write};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::thread;
+
+const USAGE: &str = "Usage: {bin} <fastq_file|-|fofn> [--threads N] [--fofn] [--to-fasta] [--min-len N] [--wrap N] [--index] [--fetch ID]";
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    
+
     if args.len() < 2 {
-        eprintln!("Usage: {} <fastq_file>", args[0]);
+        eprintln!("{}", USAGE.replace("{bin}", &args[0]));
         std::process::exit(1);
     }
 
-    let filename = &args[1];
-    
-    let mut n_seqs = 0u64;
-    let mut n_bases = 0u64;
-    
-    let mut reader = parse_fastx_file(filename).unwrap_or_else(|e| {
-        eprintln!("Error opening file '{}': {}", filename, e);
+    let mut filename = None;
+    let mut n_threads = 1usize;
+    let mut is_fofn = false;
+    let mut to_fasta = false;
+    let mut min_len = 0usize;
+    let mut wrap = 70usize;
+    let mut build_index_flag = false;
+    let mut fetch_id = None;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--threads" => {
+                i += 1;
+                n_threads = args.get(i).and_then(|v| v.parse().ok()).unwrap_or_else(|| {
+                    eprintln!("--threads requires a numeric argument");
+                    std::process::exit(1);
+                });
+            }
+            "--fofn" => is_fofn = true,
+            "--to-fasta" => to_fasta = true,
+            "--min-len" => {
+                i += 1;
+                min_len = args.get(i).and_then(|v| v.parse().ok()).unwrap_or_else(|| {
+                    eprintln!("--min-len requires a numeric argument");
+                    std::process::exit(1);
+                });
+            }
+            "--wrap" => {
+                i += 1;
+                wrap = args.get(i).and_then(|v| v.parse().ok()).unwrap_or_else(|| {
+                    eprintln!("--wrap requires a numeric argument");
+                    std::process::exit(1);
+                });
+            }
+            "--index" => build_index_flag = true,
+            "--fetch" => {
+                i += 1;
+                fetch_id = Some(args.get(i).cloned().unwrap_or_else(|| {
+                    eprintln!("--fetch requires a record id argument");
+                    std::process::exit(1);
+                }));
+            }
+            other if other.starts_with("--") => {
+                eprintln!("Unrecognized option '{}'", other);
+                eprintln!("{}", USAGE.replace("{bin}", &args[0]));
+                std::process::exit(1);
+            }
+            other => {
+                if filename.is_some() {
+                    eprintln!("Unexpected extra argument '{}'", other);
+                    eprintln!("{}", USAGE.replace("{bin}", &args[0]));
+                    std::process::exit(1);
+                }
+                filename = Some(other.to_string());
+            }
+        }
+        i += 1;
+    }
+
+    let filename = filename.unwrap_or_else(|| {
+        eprintln!("{}", USAGE.replace("{bin}", &args[0]));
+        std::process::exit(1);
+    });
+    is_fofn = is_fofn || filename.ends_with(".fofn");
+
+    if build_index_flag {
+        build_index(&filename).unwrap_or_else(|e| {
+            eprintln!("Error building index for '{}': {}", filename, e);
+            std::process::exit(1);
+        });
+        return;
+    }
+
+    if let Some(id) = fetch_id {
+        match fetch(&filename, &id) {
+            Ok(Some((rec_id, seq, qual))) => {
+                let mut writer = Writer::new(io::stdout(), 0);
+                writer.write_fastq(&rec_id, &seq, &qual).unwrap_or_else(|e| {
+                    eprintln!("Error writing record: {}", e);
+                    std::process::exit(1);
+                });
+            }
+            Ok(None) => {
+                eprintln!("Record '{}' not found in index for '{}'", id, filename);
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Error fetching '{}' from '{}': {}", id, filename, e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if is_fofn {
+        run_fofn(&filename, n_threads);
+        return;
+    }
+
+    if to_fasta || min_len > 0 {
+        run_filter(&filename, min_len, to_fasta, wrap);
+        return;
+    }
+
+    let (n_seqs, n_bases) = if n_threads > 1 && parallel_eligible(&filename) {
+        count_parallel(&filename, n_threads)
+    } else {
+        count_serial(&filename)
+    };
+
+    println!("{}\t{}", n_seqs, n_bases);
+    io::stdout().flush().unwrap();
+}
+
+/// FASTX input formats, auto-detected from the first non-whitespace byte:
+/// `>` starts a FASTA record, `@` starts a FASTQ record.
+enum Format {
+    Fasta,
+    Fastq,
+}
+
+/// Peeks the first non-whitespace byte of `path` to tell FASTA from
+/// FASTQ. Only meaningful for a real, uncompressed file; gzip framing
+/// hides the record marker, so compressed input falls back to
+/// per-record detection in `run_filter`.
+fn sniff_format(path: &str) -> Format {
+    let file = File::open(path).unwrap_or_else(|e| {
+        eprintln!("Error opening file '{}': {}", path, e);
+        std::process::exit(1);
+    });
+    let mut reader = BufReader::new(file);
+    let mut byte = [0u8; 1];
+    loop {
+        match reader.read(&mut byte) {
+            Ok(0) | Err(_) => return Format::Fasta,
+            Ok(_) => match byte[0] {
+                b'>' => return Format::Fasta,
+                b'@' => return Format::Fastq,
+                b' ' | b'\t' | b'\r' | b'\n' => continue,
+                _ => return Format::Fasta,
+            },
+        }
+    }
+}
+
+/// Reads FASTX records from `path`, applies `--min-len`/`--to-fasta`
+/// filtering, and writes the survivors to stdout. The output format
+/// mirrors the input unless `to_fasta` is set, falling back to a
+/// per-record check (does it have quality scores?) when the input is
+/// stdin or a `.gz`/`.bgz` file and can't be sniffed up front.
+fn run_filter(path: &str, min_len: usize, to_fasta: bool, wrap: usize) {
+    let sniffed = if to_fasta || path == "-" || is_compressed_path(path) {
+        None
+    } else {
+        Some(sniff_format(path))
+    };
+
+    let mut reader = open_reader(path);
+    let mut writer = Writer::new(io::stdout(), wrap);
+
+    while let Some(record) = reader.next() {
+        let record = record.unwrap_or_else(|e| {
+            eprintln!("Error parsing record: {}", e);
+            std::process::exit(1);
+        });
+
+        if record.num_bases() < min_len {
+            continue;
+        }
+
+        let write_fasta = to_fasta
+            || match sniffed {
+                Some(Format::Fasta) => true,
+                Some(Format::Fastq) => false,
+                None => record.qual().is_none(),
+            };
+
+        let result = if write_fasta {
+            writer.write_fasta(record.id(), &record.seq())
+        } else {
+            writer.write_fastq(record.id(), &record.seq(), record.qual().unwrap_or(b""))
+        };
+        result.unwrap_or_else(|e| {
+            eprintln!("Error writing record: {}", e);
+            std::process::exit(1);
+        });
+    }
+
+    writer.flush().unwrap_or_else(|e| {
+        eprintln!("Error flushing output: {}", e);
         std::process::exit(1);
     });
-    
+}
+
+/// Writes FASTA/FASTQ records to an underlying `Write`, wrapping FASTA
+/// sequence lines at `wrap` columns (0 disables wrapping).
+struct Writer<W: Write> {
+    inner: W,
+    wrap: usize,
+}
+
+impl<W: Write> Writer<W> {
+    fn new(inner: W, wrap: usize) -> Self {
+        Writer { inner, wrap }
+    }
+
+    fn write_fasta(&mut self, id: &[u8], seq: &[u8]) -> io::Result<()> {
+        self.inner.write_all(b">")?;
+        self.inner.write_all(id)?;
+        self.inner.write_all(b"\n")?;
+        if self.wrap == 0 {
+            self.inner.write_all(seq)?;
+            self.inner.write_all(b"\n")?;
+        } else {
+            for line in seq.chunks(self.wrap) {
+                self.inner.write_all(line)?;
+                self.inner.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_fastq(&mut self, id: &[u8], seq: &[u8], qual: &[u8]) -> io::Result<()> {
+        self.inner.write_all(b"@")?;
+        self.inner.write_all(id)?;
+        self.inner.write_all(b"\n")?;
+        self.inner.write_all(seq)?;
+        self.inner.write_all(b"\n+\n")?;
+        self.inner.write_all(qual)?;
+        self.inner.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Dispatches on the input spec: `-` reads FASTQ/FASTA from stdin,
+/// anything else is opened as a normal (optionally gzipped) file.
+///
+/// A fofn is deliberately *not* handled here: it names a list of files,
+/// not a single FASTX stream, so it can't be expanded into one
+/// `FastxReader`. `main`/`run_fofn` detect `.fofn`/`--fofn` themselves
+/// and call `open_reader` once per listed file instead. Passing a fofn
+/// path straight to `open_reader` will parse it as a malformed FASTX
+/// file rather than as a file list.
+fn open_reader(path: &str) -> Box<dyn FastxReader> {
+    if path == "-" {
+        parse_fastx_stdin().unwrap_or_else(|e| {
+            eprintln!("Error reading from stdin: {}", e);
+            std::process::exit(1);
+        })
+    } else {
+        parse_fastx_file(path).unwrap_or_else(|e| {
+            eprintln!("Error opening file '{}': {}", path, e);
+            std::process::exit(1);
+        })
+    }
+}
+
+/// The original single-threaded counting path, used whenever the input
+/// can't be memory-mapped (stdin, plain gzip, etc.) or `--threads` is 1.
+fn count_serial(path: &str) -> (u64, u64) {
+    count_from_reader(open_reader(path))
+}
+
+/// Drains a reader to completion, tallying sequences and bases.
+fn count_from_reader(mut reader: Box<dyn FastxReader>) -> (u64, u64) {
+    let mut n_seqs = 0u64;
+    let mut n_bases = 0u64;
+
     while let Some(record) = reader.next() {
         let record = record.unwrap_or_else(|e| {
             eprintln!("Error parsing record: {}", e);
             std::process::exit(1);
         });
-        
+
         n_seqs += 1;
         n_bases += record.num_bases() as u64;
     }
-    
-    println!("{}\t{}", n_seqs, n_bases);
+
+    (n_seqs, n_bases)
+}
+
+/// Tallies every file listed in a fofn (one path per line, each possibly
+/// gzipped), printing a per-file line as it goes plus a final cumulative
+/// total. Mirrors how users already batch sequencing runs.
+fn run_fofn(fofn_path: &str, n_threads: usize) {
+    let file = File::open(fofn_path).unwrap_or_else(|e| {
+        eprintln!("Error opening fofn '{}': {}", fofn_path, e);
+        std::process::exit(1);
+    });
+
+    let mut total_seqs = 0u64;
+    let mut total_bases = 0u64;
+
+    for line in BufReader::new(file).lines() {
+        let line = line.unwrap_or_else(|e| {
+            eprintln!("Error reading fofn '{}': {}", fofn_path, e);
+            std::process::exit(1);
+        });
+        let path = line.trim();
+        if path.is_empty() {
+            continue;
+        }
+
+        let (n_seqs, n_bases) = if n_threads > 1 && parallel_eligible(path) {
+            count_parallel(path, n_threads)
+        } else {
+            count_serial(path)
+        };
+
+        println!("{}\t{}\t{}", path, n_seqs, n_bases);
+        total_seqs += n_seqs;
+        total_bases += n_bases;
+    }
+
+    println!("total\t{}\t{}", total_seqs, total_bases);
     io::stdout().flush().unwrap();
 }
+
+/// True if `path` can be safely memory-mapped and carved into independent
+/// chunks: a real seekable, uncompressed file. `count_parallel` has no
+/// decompression step, so any compressed input (including BGZF, despite
+/// being block-seekable in principle) falls back to `count_serial`
+/// rather than advertising parallel support it doesn't implement.
+fn mmap_eligible(path: &str) -> bool {
+    path != "-" && !is_compressed_path(path)
+}
+
+/// True if `path`'s extension marks it as gzip/BGZF-compressed. Shared by
+/// `mmap_eligible` (which falls back to `count_serial` for any compressed
+/// input) and `build_index` (which rejects any compression outright,
+/// since `IndexedReader` only ever reads raw bytes).
+fn is_compressed_path(path: &str) -> bool {
+    path.ends_with(".gz") || path.ends_with(".bgz")
+}
+
+/// True if `path` can take the `count_parallel` path: mmap-eligible and
+/// FASTQ. The virtual chunk splitter only knows how to find FASTQ record
+/// boundaries, so FASTA input always falls back to `count_serial`.
+fn parallel_eligible(path: &str) -> bool {
+    mmap_eligible(path) && matches!(sniff_format(path), Format::Fastq)
+}
+
+/// Counts sequences and bases in `path` by memory-mapping the file and
+/// splitting it into `n_threads` virtual chunks, each scanned forward to
+/// the next well-formed FASTQ record boundary before parsing begins.
+/// FASTQ-only; callers must route FASTA (and anything `mmap_eligible`
+/// doesn't clear) through `count_serial` instead.
+pub fn count_parallel(path: &str, n_threads: usize) -> (u64, u64) {
+    let file = File::open(path).unwrap_or_else(|e| {
+        eprintln!("Error opening file '{}': {}", path, e);
+        std::process::exit(1);
+    });
+    let mmap = unsafe { Mmap::map(&file) }.unwrap_or_else(|e| {
+        eprintln!("Error mapping file '{}': {}", path, e);
+        std::process::exit(1);
+    });
+    let data: &[u8] = &mmap;
+    let len = data.len();
+    let n_threads = n_threads.max(1);
+
+    let mut bounds = Vec::with_capacity(n_threads + 1);
+    bounds.push(0);
+    for i in 1..n_threads {
+        let naive = len * i / n_threads;
+        bounds.push(find_record_boundary(data, naive));
+    }
+    bounds.push(len);
+    bounds.dedup();
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = bounds
+            .windows(2)
+            .map(|w| {
+                let (start, end) = (w[0], w[1]);
+                scope.spawn(move || count_chunk(&data[start..end]))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .fold((0u64, 0u64), |acc, x| (acc.0 + x.0, acc.1 + x.1))
+    })
+}
+
+/// Scans forward from `from` for the start of a well-formed FASTQ record,
+/// disambiguating a genuine header from an `@`/`+` byte inside a quality
+/// string by requiring the next three lines to look like seq/`+`/qual.
+fn find_record_boundary(data: &[u8], from: usize) -> usize {
+    let len = data.len();
+    let mut pos = from;
+    while pos < len {
+        if data[pos] == b'@' && (pos == 0 || data[pos - 1] == b'\n') && validate_fastq_record(data, pos) {
+            return pos;
+        }
+        match data[pos..].iter().position(|&b| b == b'\n') {
+            Some(off) => pos += off + 1,
+            None => return len,
+        }
+    }
+    len
+}
+
+/// Returns true if the four lines starting at `pos` look like a FASTQ
+/// record: `@` header, sequence, `+` separator, and a quality line whose
+/// length matches the sequence.
+fn validate_fastq_record(data: &[u8], pos: usize) -> bool {
+    let mut cur = pos;
+    let mut lines: [&[u8]; 4] = [&[]; 4];
+    for line in lines.iter_mut() {
+        let nl = match data[cur..].iter().position(|&b| b == b'\n') {
+            Some(off) => off,
+            None => return false,
+        };
+        *line = &data[cur..cur + nl];
+        cur += nl + 1;
+    }
+    lines[0].first() == Some(&b'@') && lines[2].first() == Some(&b'+') && lines[1].len() == lines[3].len()
+}
+
+/// Counts sequences and bases in a raw chunk of FASTQ bytes, four lines
+/// per record. Assumes the chunk starts on a record boundary.
+fn count_chunk(chunk: &[u8]) -> (u64, u64) {
+    let mut lines = chunk.split(|&b| b == b'\n');
+    let mut n_seqs = 0u64;
+    let mut n_bases = 0u64;
+    loop {
+        let header = match lines.next() {
+            Some(l) if !l.is_empty() => l,
+            _ => break,
+        };
+        let seq = match lines.next() {
+            Some(l) => l,
+            None => break,
+        };
+        let _plus = match lines.next() {
+            Some(l) => l,
+            None => break,
+        };
+        let _qual = match lines.next() {
+            Some(l) => l,
+            None => break,
+        };
+        if header.first() == Some(&b'@') {
+            n_seqs += 1;
+            let seq_len = if seq.last() == Some(&b'\r') { seq.len() - 1 } else { seq.len() };
+            n_bases += seq_len as u64;
+        }
+    }
+    (n_seqs, n_bases)
+}
+
+/// A parsed FASTQ record as `(id, seq, qual)`, with the leading `@` and
+/// trailing newlines already stripped.
+type FastqRecord = (Vec<u8>, Vec<u8>, Vec<u8>);
+
+/// The byte offset and ordinal of a single record, captured before the
+/// reader advances past it, so it can be handed to `IndexedReader::seek`
+/// later to jump straight back without rescanning.
+pub struct Position {
+    pub byte_offset: u64,
+    pub record_number: u64,
+}
+
+/// A FASTQ reader over a seekable file that tracks each record's byte
+/// offset and can seek back to any previously recorded `Position`,
+/// enabling indexed random access over large files.
+pub struct IndexedReader {
+    reader: BufReader<File>,
+    offset: u64,
+    record_number: u64,
+}
+
+impl IndexedReader {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(IndexedReader {
+            reader: BufReader::new(file),
+            offset: 0,
+            record_number: 0,
+        })
+    }
+
+    /// The position of the record that the next `next_record` call will
+    /// return, captured before the reader advances past it.
+    pub fn record_position(&self) -> Position {
+        Position {
+            byte_offset: self.offset,
+            record_number: self.record_number,
+        }
+    }
+
+    /// Repositions the underlying file and resets parser state so the
+    /// next `next_record` call yields the record at `pos`.
+    pub fn seek(&mut self, pos: &Position) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(pos.byte_offset))?;
+        self.offset = pos.byte_offset;
+        self.record_number = pos.record_number;
+        Ok(())
+    }
+
+    /// Reads the next FASTQ record as `(id, seq, qual)`, with the leading
+    /// `@` and trailing newlines stripped. Returns `None` at EOF, and an
+    /// error if the file ends mid-record.
+    pub fn next_record(&mut self) -> io::Result<Option<FastqRecord>> {
+        let mut header = Vec::new();
+        if self.reader.read_until(b'\n', &mut header)? == 0 {
+            return Ok(None);
+        }
+        let mut seq = Vec::new();
+        let mut plus = Vec::new();
+        let mut qual = Vec::new();
+        if self.reader.read_until(b'\n', &mut seq)? == 0
+            || self.reader.read_until(b'\n', &mut plus)? == 0
+            || self.reader.read_until(b'\n', &mut qual)? == 0
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated FASTQ record",
+            ));
+        }
+
+        self.offset += (header.len() + seq.len() + plus.len() + qual.len()) as u64;
+        self.record_number += 1;
+
+        trim_newline(&mut seq);
+        trim_newline(&mut qual);
+        trim_newline(&mut header);
+        let id = if header.first() == Some(&b'@') {
+            header[1..].to_vec()
+        } else {
+            header
+        };
+        Ok(Some((id, seq, qual)))
+    }
+}
+
+/// Strips a trailing `\n` or `\r\n` in place.
+fn trim_newline(buf: &mut Vec<u8>) {
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+        if buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+    }
+}
+
+/// Builds a record-id to byte-offset index for `path` in one pass and
+/// writes it to `<path>.idx` as tab-separated lines, so `fetch` can pull
+/// a single record later without rescanning the whole file. Only plain,
+/// uncompressed FASTQ is supported: `IndexedReader` reads raw bytes, so
+/// stdin and any `.gz`/`.bgz` input (even BGZF) are rejected up front.
+pub fn build_index(path: &str) -> io::Result<()> {
+    if path == "-" || is_compressed_path(path) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--index only supports plain, uncompressed FASTQ input (IndexedReader does not decompress)",
+        ));
+    }
+    if !matches!(sniff_format(path), Format::Fastq) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--index only supports FASTQ input",
+        ));
+    }
+
+    let mut reader = IndexedReader::open(path)?;
+    let mut index_file = BufWriter::new(File::create(format!("{}.idx", path))?);
+
+    loop {
+        let pos = reader.record_position();
+        match reader.next_record()? {
+            Some((id, _seq, _qual)) => {
+                let id = String::from_utf8_lossy(&id);
+                let id = id.split_whitespace().next().unwrap_or("");
+                writeln!(index_file, "{}\t{}", id, pos.byte_offset)?;
+            }
+            None => break,
+        }
+    }
+
+    index_file.flush()?;
+    Ok(())
+}
+
+/// Looks up `id` in the `<path>.idx` index built by `build_index` and
+/// reads just that one record, avoiding a full rescan of `path`.
+pub fn fetch(path: &str, id: &str) -> io::Result<Option<FastqRecord>> {
+    let index_file = File::open(format!("{}.idx", path))?;
+    let mut byte_offset = None;
+    for line in BufReader::new(index_file).lines() {
+        let line = line?;
+        if let Some((rec_id, offset)) = line.split_once('\t') {
+            if rec_id == id {
+                byte_offset = offset.parse::<u64>().ok();
+                break;
+            }
+        }
+    }
+    let byte_offset = match byte_offset {
+        Some(o) => o,
+        None => return Ok(None),
+    };
+
+    let mut reader = IndexedReader::open(path)?;
+    reader.seek(&Position { byte_offset, record_number: 0 })?;
+    reader.next_record()
+}